@@ -17,7 +17,7 @@ use dialoguer::Select;
 use levenshtein::levenshtein;
 use murmur2::{murmur2, KAFKA_SEED};
 use rdkafka::consumer::{Consumer, ConsumerContext};
-use rdkafka::metadata::Metadata;
+use regex::Regex;
 use tokio::runtime::Handle;
 use tokio::time::timeout;
 use crate::app::KiekException;
@@ -27,13 +27,91 @@ use crate::highlight::Highlighting;
 /// Timeout for the Kafka operations
 const TIMEOUT: Duration = Duration::from_secs(10);
 
+/// A topic and its partition count, as returned by [`MetadataSource::fetch_topics`]
+pub(crate) struct TopicInfo {
+    pub name: String,
+    pub partitions: usize,
+}
+
+///
+/// Abstraction over the broker metadata lookups kiek's topic-selection logic needs, so that logic
+/// can be unit-tested against an in-memory fake instead of a real Kafka cluster.
+/// `StreamConsumer<Ctx>` is the production implementation.
+///
+pub(crate) trait MetadataSource {
+    /// Lists the topic named `topic`, or every topic in the cluster when `None`.
+    fn fetch_topics(&self, topic: Option<&str>) -> Result<Vec<TopicInfo>>;
+}
+
+///
+/// Abstraction over the handful of broker calls kiek's partition-assignment and offset-resolution
+/// logic needs, so that logic can be unit-tested against an in-memory fake instead of a real Kafka
+/// cluster. `StreamConsumer<Ctx>` is the production implementation.
+///
+pub(crate) trait PartitionAssigner: MetadataSource {
+    /// Assigns the given partitions, each seeded at the given offset, to the consumer.
+    fn assign(&self, assignment: &HashMap<(String, i32), Offset>) -> Result<()>;
+    /// Resolves a millisecond timestamp into a concrete per-partition offset via `offsets_for_times`.
+    fn offsets_for_times(&self, topic: &str, partitions: &[i32], timestamp_ms: i64) -> Result<HashMap<i32, Offset>>;
+    /// Looks up the committed offset of the consumer group for each given partition.
+    fn committed_offsets(&self, topic: &str, partitions: &[i32]) -> Result<HashMap<i32, Offset>>;
+    /// Looks up the low and high watermark (the earliest and the next-to-be-written offset) of a partition.
+    fn fetch_watermarks(&self, topic: &str, partition: i32) -> Result<(i64, i64)>;
+}
+
+impl<Ctx> MetadataSource for StreamConsumer<Ctx>
+where
+    Ctx: ConsumerContext + 'static,
+{
+    fn fetch_topics(&self, topic: Option<&str>) -> Result<Vec<TopicInfo>> {
+        let metadata = Consumer::fetch_metadata(self, topic, TIMEOUT)?;
+        Ok(metadata.topics().iter().map(|t| TopicInfo { name: t.name().to_string(), partitions: t.partitions().len() }).collect())
+    }
+}
+
+impl<Ctx> PartitionAssigner for StreamConsumer<Ctx>
+where
+    Ctx: ConsumerContext + 'static,
+{
+    fn assign(&self, assignment: &HashMap<(String, i32), Offset>) -> Result<()> {
+        let topic_partition_list = TopicPartitionList::from_topic_map(assignment)?;
+        Consumer::assign(self, &topic_partition_list)?;
+        Ok(())
+    }
+
+    fn offsets_for_times(&self, topic: &str, partitions: &[i32], timestamp_ms: i64) -> Result<HashMap<i32, Offset>> {
+        let mut list = TopicPartitionList::new();
+        for &partition in partitions {
+            list.add_partition_offset(topic, partition, Offset::Offset(timestamp_ms))?;
+        }
+        let resolved = Consumer::offsets_for_times(self, list, TIMEOUT)?;
+        Ok(resolved.elements().iter().filter(|element| element.topic() == topic).map(|element| (element.partition(), element.offset())).collect())
+    }
+
+    fn committed_offsets(&self, topic: &str, partitions: &[i32]) -> Result<HashMap<i32, Offset>> {
+        let mut list = TopicPartitionList::new();
+        for &partition in partitions {
+            list.add_partition(topic, partition);
+        }
+        let committed = Consumer::committed_offsets(self, list, TIMEOUT)?;
+        Ok(committed.elements().iter().filter(|element| element.topic() == topic).map(|element| (element.partition(), element.offset())).collect())
+    }
+
+    fn fetch_watermarks(&self, topic: &str, partition: i32) -> Result<(i64, i64)> {
+        Ok(Consumer::fetch_watermarks(self, topic, partition, TIMEOUT)?)
+    }
+}
+
 ///
-/// Create basic client configuration with the provided bootstrap servers
+/// Create basic client configuration with the provided bootstrap servers.
 ///
-pub fn create_config<S: Into<String>>(bootstrap_servers: S) -> ClientConfig {
+/// `group_id` defaults to kiek's own `"kieker"` group; pass the id of an existing consumer group
+/// to inspect its committed offsets instead (see [`inspect_consumer_group`]).
+///
+pub fn create_config<S: Into<String>>(bootstrap_servers: S, group_id: Option<&str>) -> ClientConfig {
     let mut client_config = ClientConfig::new();
     client_config.set("bootstrap.servers", bootstrap_servers);
-    client_config.set("group.id", "kieker");
+    client_config.set("group.id", group_id.unwrap_or("kieker"));
     client_config.set("enable.auto.commit", "false");
     client_config
 }
@@ -41,8 +119,8 @@ pub fn create_config<S: Into<String>>(bootstrap_servers: S) -> ClientConfig {
 ///
 /// Create a Kafka consumer to connect to an MSK cluster with IAM authentication.
 ///
-pub async fn create_msk_consumer(bootstrap_servers: &String, credentials_provider: SharedCredentialsProvider, region: Region, feedback: &Feedback) -> Result<StreamConsumer<IamContext>> {
-    let mut client_config = create_config(bootstrap_servers);
+pub async fn create_msk_consumer(bootstrap_servers: &String, group_id: Option<&str>, credentials_provider: SharedCredentialsProvider, region: Region, feedback: &Feedback) -> Result<StreamConsumer<IamContext>> {
+    let mut client_config = create_config(bootstrap_servers, group_id);
     client_config.set("security.protocol", "SASL_SSL");
     client_config.set("sasl.mechanism", "OAUTHBEARER");
 
@@ -67,10 +145,12 @@ where
 }
 
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, Clone)]
 pub(crate) enum TopicOrPartition {
     Topic(String),
     TopicPartition(String, usize),
+    /// Every topic whose name matches the regular expression, e.g. `^orders\..*`
+    Pattern(Regex),
 }
 
 impl TopicOrPartition {
@@ -78,6 +158,18 @@ impl TopicOrPartition {
         match self {
             TopicOrPartition::Topic(topic) => topic,
             TopicOrPartition::TopicPartition(topic, _) => topic,
+            TopicOrPartition::Pattern(pattern) => pattern.as_str(),
+        }
+    }
+}
+
+impl PartialEq for TopicOrPartition {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (TopicOrPartition::Topic(a), TopicOrPartition::Topic(b)) => a == b,
+            (TopicOrPartition::TopicPartition(a, pa), TopicOrPartition::TopicPartition(b, pb)) => a == b && pa == pb,
+            (TopicOrPartition::Pattern(a), TopicOrPartition::Pattern(b)) => a.as_str() == b.as_str(),
+            _ => false,
         }
     }
 }
@@ -86,6 +178,10 @@ impl TopicOrPartition {
 pub(crate) enum StartOffset {
     Earliest,
     Latest(i64),
+    /// Start at the first message at or after this wall-clock timestamp, resolved per partition via `offsets_for_times`
+    Timestamp(DateTime<Local>),
+    /// An offset already resolved for a specific partition, e.g. the result of `offsets_for_times`
+    Resolved(Offset),
 }
 
 ///
@@ -93,22 +189,27 @@ pub(crate) enum StartOffset {
 /// Calculates the partition for the given key and assigns it to the consumer with given offset configuration.
 ///
 
-pub async fn assign_partition_for_key<Ctx>(consumer: &StreamConsumer<Ctx>, topic_or_partition: &TopicOrPartition, key: &str, start_offset: StartOffset, feedback: &Feedback) -> Result<()>
+pub async fn assign_partition_for_key<B>(broker: &B, topic_or_partition: &TopicOrPartition, key: &str, partitioner: Partitioner, start_offset: StartOffset, feedback: &Feedback) -> Result<()>
 where
-    Ctx: ConsumerContext + 'static,
+    B: PartitionAssigner,
 {
+    if let TopicOrPartition::Pattern(pattern) = topic_or_partition {
+        return Err(KiekException::boxed(format!("Cannot look up key {key} in a topic pattern ({pattern}). Please specify a single topic.", pattern = pattern.as_str())));
+    }
+
     let topic = topic_or_partition.topic();
 
-    let num_partitions = fetch_number_of_partitions(consumer, topic, &feedback.highlighting).await?;
+    let num_partitions = fetch_number_of_partitions(broker, topic, &feedback.highlighting).await?;
 
-    let partition = partition_for_key(key, num_partitions);
+    let partition = partition_for_key(key, num_partitions, partitioner);
 
     let partition =
         match topic_or_partition {
             TopicOrPartition::Topic(_) => partition,
             TopicOrPartition::TopicPartition(_, configured_partition) => {
                 if partition != *configured_partition {
-                    feedback.warning(format!("Key {bold}{key}{bold:#} would be expected in partition {success}{partition}{success:#} with default partitioning, not in configured partition {error}{configured_partition}{error:#}.",
+                    feedback.warning(format!("Key {bold}{key}{bold:#} would be expected in partition {success}{partition}{success:#} with {strategy} partitioning, not in configured partition {error}{configured_partition}{error:#}.",
+                                             strategy = partitioner.name(),
                                              bold = feedback.highlighting.bold,
                                              success = feedback.highlighting.success,
                                              error = feedback.highlighting.error))
@@ -117,13 +218,13 @@ where
             }
         };
 
+    let offset = resolve_offset(broker, topic, partition as i32, &start_offset).await?;
     let partition_offsets: HashMap<(String, i32), Offset> =
-        HashMap::from([((topic.to_string(), partition as i32), offset_for(start_offset))]);
+        HashMap::from([((topic.to_string(), partition as i32), offset)]);
 
     info!("Assigning partition {partition} for {topic} to search for key {key}.");
 
-    let topic_partition_list = TopicPartitionList::from_topic_map(&partition_offsets)?;
-    consumer.assign(&topic_partition_list)?;
+    broker.assign(&partition_offsets)?;
 
     Ok(())
 }
@@ -136,30 +237,29 @@ where
 ///
 /// Fail if there is no topic at all or in silent mode if topic choice is ambiguous.
 ///
-pub async fn select_topic_or_partition<Ctx>(consumer: &StreamConsumer<Ctx>, feedback: &Feedback) -> Result<TopicOrPartition>
+pub async fn select_topic_or_partition<B>(broker: &B, feedback: &Feedback) -> Result<TopicOrPartition>
 where
-    Ctx: ConsumerContext + 'static,
+    B: MetadataSource,
 {
-    let metadata = consumer.fetch_metadata(None, TIMEOUT)?;
-    let topic_names: Vec<String> = metadata.topics().iter().map(|t| t.name().to_string()).collect();
+    let topics = broker.fetch_topics(None)?;
 
-    if topic_names.len() == 0 {
+    if topics.is_empty() {
         return Err(KiekException::boxed("No topics available in the Kafka cluster."));
-    } else if topic_names.len() == 1 {
-        feedback.info("Using", format!("topic {topic}", topic = &topic_names[0]));
-        return Ok(TopicOrPartition::Topic(topic_names[0].clone()));
+    } else if topics.len() == 1 {
+        feedback.info("Using", format!("topic {topic}", topic = &topics[0].name));
+        return Ok(TopicOrPartition::Topic(topics[0].name.clone()));
     } else if feedback.silent {
         return Err(KiekException::boxed("Multiple topics available in the Kafka cluster. Please specify a topic."));
     } else {
-        prompt_topic_or_partition(&metadata, &feedback)
+        prompt_topic_or_partition(&topics, &feedback)
     }
 }
 
 ///
 /// Prompt the user to select a topic or partition from the Kafka cluster.
 ///
-fn prompt_topic_or_partition(metadata: &Metadata, feedback: &Feedback) -> Result<TopicOrPartition> {
-    let topic_names: Vec<String> = metadata.topics().iter().map(|t| t.name().to_string()).collect();
+fn prompt_topic_or_partition(topics: &[TopicInfo], feedback: &Feedback) -> Result<TopicOrPartition> {
+    let topic_names: Vec<String> = topics.iter().map(|t| t.name.clone()).collect();
 
     feedback.clear();
     let theme = feedback.highlighting.dialoguer_theme();
@@ -170,18 +270,18 @@ fn prompt_topic_or_partition(metadata: &Metadata, feedback: &Feedback) -> Result
         .interact()
         .unwrap();
 
-    let topic = &topic_names[selection];
+    let topic = &topics[selection];
 
     let partitions: Vec<String> =
-        metadata.topics().iter().filter(|t| t.name() == topic).flat_map(|t| (-1..t.partitions().len() as i32).map(|p|
+        (-1..topic.partitions as i32).map(|p|
             if p == -1 {
-                format!("{} (all partitions)", t.name())
+                format!("{} (all partitions)", topic.name)
             } else {
-                format!("{color}{}{color:#}{dimmed}-{dimmed:#}{color}{}{color:#}", t.name(), p,
+                format!("{color}{}{color:#}{dimmed}-{dimmed:#}{color}{}{color:#}", topic.name, p,
                         color = feedback.highlighting.partition(p),
                         dimmed = feedback.highlighting.partition(p).dimmed())
             }
-        )).collect();
+        ).collect();
 
     let partition = Select::with_theme(&*theme)
         .with_prompt("Select a partition")
@@ -192,9 +292,9 @@ fn prompt_topic_or_partition(metadata: &Metadata, feedback: &Feedback) -> Result
         .unwrap() as i32 - 1;
 
     if partition == -1 {
-        Ok(TopicOrPartition::Topic(topic.clone()))
+        Ok(TopicOrPartition::Topic(topic.name.clone()))
     } else {
-        Ok(TopicOrPartition::TopicPartition(topic.clone(), partition as usize))
+        Ok(TopicOrPartition::TopicPartition(topic.name.clone(), partition as usize))
     }
 }
 
@@ -203,43 +303,138 @@ fn prompt_topic_or_partition(metadata: &Metadata, feedback: &Feedback) -> Result
 /// If a partition is given and valid, it assigns it to the consumer with given offset configuration.
 /// If a topic is given, it assigns all partitions to the consumer with given offset configuration.
 ///
-pub async fn assign_topic_or_partition<Ctx>(consumer: &StreamConsumer<Ctx>, topic_or_partition: &TopicOrPartition, start_offset: StartOffset, highlighting: &Highlighting) -> Result<()>
+pub async fn assign_topic_or_partition<B>(broker: &B, topic_or_partition: &TopicOrPartition, start_offset: StartOffset, highlighting: &Highlighting) -> Result<()>
 where
-    Ctx: ConsumerContext + 'static,
+    B: PartitionAssigner,
 {
-    let topic = topic_or_partition.topic();
+    if let TopicOrPartition::Pattern(pattern) = topic_or_partition {
+        return assign_pattern(broker, pattern, start_offset, highlighting).await;
+    }
 
-    let num_partitions = fetch_number_of_partitions(consumer, topic, highlighting).await?;
+    let topic = topic_or_partition.topic();
 
-    let offset = offset_for(start_offset);
+    let num_partitions = fetch_number_of_partitions(broker, topic, highlighting).await?;
 
-    let topic_partition_list: TopicPartitionList =
+    let partition_offsets: HashMap<(String, i32), Offset> =
         match topic_or_partition {
             TopicOrPartition::Topic(_) => {
-                let partition_offsets: HashMap<(String, i32), Offset> =
-                    (0..num_partitions)
-                        .map(|partition| { ((topic.to_string(), partition as i32), offset) })
-                        .collect();
+                let partitions: Vec<i32> = (0..num_partitions as i32).collect();
+                let partition_offsets = resolve_offsets(broker, topic, &partitions, &start_offset).await?;
 
                 info!("Assigning all {num_partitions} partitions for {topic}.");
 
-                TopicPartitionList::from_topic_map(&partition_offsets)?
+                partition_offsets
             }
             TopicOrPartition::TopicPartition(_, partition) => {
                 if *partition > num_partitions {
                     return Err(KiekException::boxed(format!("Partition {partition} is out of range for {topic} with {num_partitions} partitions.")));
                 }
 
-                let partition_offsets: HashMap<(String, i32), Offset> =
-                    HashMap::from([((topic.to_string(), *partition as i32), offset)]);
+                let offset = resolve_offset(broker, topic, *partition as i32, &start_offset).await?;
 
                 info!("Assigning partition {partition} for {topic}.");
 
-                TopicPartitionList::from_topic_map(&partition_offsets)?
+                HashMap::from([((topic.to_string(), *partition as i32), offset)])
             }
         };
 
-    consumer.assign(&topic_partition_list)?;
+    broker.assign(&partition_offsets)?;
+
+    Ok(())
+}
+
+///
+/// Resolves a regular expression against every topic in the cluster and assigns all partitions of
+/// all matching topics with the given offset configuration.
+///
+/// librdkafka's own pattern subscriptions (`subscribe` with a `^`-prefixed pattern) re-resolve the
+/// pattern against the broker's metadata cache on every rebalance, automatically picking up newly
+/// created matching topics. kiek uses `assign` rather than `subscribe` throughout, so the caller is
+/// expected to call this again whenever it refreshes metadata, to get the same effect.
+///
+async fn assign_pattern<B>(broker: &B, pattern: &Regex, start_offset: StartOffset, highlighting: &Highlighting) -> Result<()>
+where
+    B: PartitionAssigner,
+{
+    let topics = broker.fetch_topics(None)?;
+    let matching_topics: Vec<&TopicInfo> = topics.iter().filter(|t| pattern.is_match(&t.name)).collect();
+
+    if matching_topics.is_empty() {
+        return Err(KiekException::boxed(format!("No topics match pattern {error}{pattern}{error:#}. {total} topics are available in total.",
+                                                  pattern = pattern.as_str(), error = highlighting.error, total = topics.len())));
+    }
+
+    let mut partition_offsets: HashMap<(String, i32), Offset> = HashMap::new();
+    for topic_info in &matching_topics {
+        let partitions: Vec<i32> = (0..topic_info.partitions as i32).collect();
+        partition_offsets.extend(resolve_offsets(broker, &topic_info.name, &partitions, &start_offset).await?);
+    }
+
+    info!("Assigning {} partitions across {} topics matching pattern {}.", partition_offsets.len(), matching_topics.len(), pattern.as_str());
+
+    broker.assign(&partition_offsets)?;
+
+    Ok(())
+}
+
+///
+/// Non-consuming inspection mode for a consumer group: prints a per-partition table of committed
+/// offset, high watermark (end offset) and lag, without assigning or consuming any messages.
+///
+/// The broker passed in must already be configured with the group id to inspect, e.g. via
+/// `create_config(bootstrap_servers, Some(group_id))`, so that `committed_offsets` resolves
+/// offsets for that group rather than kiek's own.
+///
+pub async fn inspect_consumer_group<B>(broker: &B, bootstrap_servers: &String, topic_or_partition: &TopicOrPartition, feedback: &Feedback) -> Result<()>
+where
+    B: PartitionAssigner,
+{
+    if let TopicOrPartition::Pattern(pattern) = topic_or_partition {
+        return Err(KiekException::boxed(format!("Cannot inspect consumer group offsets for a topic pattern ({pattern}). Please specify a single topic.", pattern = pattern.as_str())));
+    }
+
+    let topic = topic_or_partition.topic();
+    let num_partitions = fetch_number_of_partitions(broker, topic, &feedback.highlighting).await?;
+
+    let partitions: Vec<i32> = match topic_or_partition {
+        TopicOrPartition::TopicPartition(_, partition) => {
+            if *partition >= num_partitions {
+                return Err(KiekException::boxed(format!("Partition {partition} is out of range for {topic} with {num_partitions} partitions.")));
+            }
+            vec![*partition as i32]
+        }
+        _ => (0..num_partitions as i32).collect(),
+    };
+
+    let committed = broker.committed_offsets(topic, &partitions)?;
+
+    feedback.info("Inspecting", format!("consumer group offsets for {topic} on {}", FormatBootstrapServers(bootstrap_servers)));
+
+    let highlighting = &feedback.highlighting;
+    for &partition in &partitions {
+        let committed_offset = committed.get(&partition)
+            .and_then(|offset| match offset {
+                Offset::Offset(offset) => Some(*offset),
+                _ => None,
+            });
+
+        let (_, high_watermark) = broker.fetch_watermarks(topic, partition)?;
+
+        let color = highlighting.partition(partition);
+        let bold = highlighting.bold;
+        let label = format!("{color}{topic}{color:#}-{color}{partition}{color:#}");
+
+        match committed_offset {
+            Some(committed_offset) => {
+                let lag = high_watermark - committed_offset;
+                let error = highlighting.error;
+                feedback.info("Partition", format!("{label}: committed {bold}{committed_offset}{bold:#}, end {bold}{high_watermark}{bold:#}, lag {error}{lag}{error:#}"));
+            }
+            None => {
+                feedback.info("Partition", format!("{label}: no committed offset, end {bold}{high_watermark}{bold:#}"));
+            }
+        }
+    }
 
     Ok(())
 }
@@ -247,17 +442,17 @@ where
 ///
 /// Fetches the number of partitions for topic with given name.
 ///
-async fn fetch_number_of_partitions<Ctx>(consumer: &StreamConsumer<Ctx>, topic: &str, highlighting: &Highlighting) -> Result<usize>
+async fn fetch_number_of_partitions<B>(broker: &B, topic: &str, highlighting: &Highlighting) -> Result<usize>
 where
-    Ctx: 'static + ConsumerContext,
+    B: MetadataSource,
 {
-    let metadata = consumer.fetch_metadata(Some(&topic), TIMEOUT)?;
+    let topics = broker.fetch_topics(Some(topic))?;
 
-    match metadata.topics().first() {
-        Some(topic_metadata) if topic_metadata.partitions().len() > 0 =>
-            Ok(topic_metadata.partitions().len()),
+    match topics.first() {
+        Some(topic_info) if topic_info.partitions > 0 =>
+            Ok(topic_info.partitions),
         _ => {
-            unknown_topic(consumer, topic, highlighting)
+            unknown_topic(broker, topic, highlighting)
         }
     }
 }
@@ -266,12 +461,12 @@ where
 /// Generates a graceful error message for an unknown topic.
 /// Looks for a very similar topic name or lists all available topics by similarity.
 ///
-fn unknown_topic<Ctx, A>(consumer: &StreamConsumer<Ctx>, topic: &str, highlighting: &Highlighting) -> Result<A>
+fn unknown_topic<B, A>(broker: &B, topic: &str, highlighting: &Highlighting) -> Result<A>
 where
-    Ctx: 'static + ConsumerContext,
+    B: MetadataSource,
 {
-    let metadata = consumer.fetch_metadata(None, TIMEOUT)?;
-    let topic_names: Vec<String> = metadata.topics().iter().map(|t| t.name().to_string()).collect();
+    let topics = broker.fetch_topics(None)?;
+    let topic_names: Vec<String> = topics.into_iter().map(|t| t.name).collect();
     Err(KiekException::boxed(unknown_topic_message(topic, &topic_names, highlighting)))
 }
 
@@ -305,11 +500,60 @@ fn unknown_topic_message(topic: &str, topic_names: &Vec<String>, highlighting: &
     }
 }
 
-fn offset_for(start_offset: StartOffset) -> Offset {
+fn offset_for(start_offset: &StartOffset) -> Offset {
     match start_offset {
         StartOffset::Earliest => Offset::Beginning,
         StartOffset::Latest(0) => Offset::End,
-        StartOffset::Latest(offset) => Offset::OffsetTail(offset),
+        StartOffset::Latest(offset) => Offset::OffsetTail(*offset),
+        StartOffset::Timestamp(_) => unreachable!("StartOffset::Timestamp must be resolved via offsets_for_times before calling offset_for"),
+        StartOffset::Resolved(offset) => *offset,
+    }
+}
+
+///
+/// Resolves the start offset for a single partition, looking up the broker-side offset for the
+/// wall-clock timestamp via `offsets_for_times` if necessary.
+///
+/// A partition whose entire log is newer than the timestamp resolves to its earliest offset, a
+/// partition with no message at or after the timestamp resolves to `Offset::End`, i.e. kiek starts
+/// tailing it without any historical data, both straight from librdkafka without further massaging.
+///
+async fn resolve_offset<B>(broker: &B, topic: &str, partition: i32, start_offset: &StartOffset) -> Result<Offset>
+where
+    B: PartitionAssigner,
+{
+    match start_offset {
+        StartOffset::Timestamp(timestamp) => {
+            let resolved = broker.offsets_for_times(topic, &[partition], timestamp.timestamp_millis())?;
+            let offset = resolved.get(&partition).copied().unwrap_or(Offset::End);
+            Ok(offset_for(&StartOffset::Resolved(offset)))
+        }
+        _ => Ok(offset_for(start_offset)),
+    }
+}
+
+///
+/// Resolves the start offset for every given partition of a topic in a single broker round trip,
+/// looking up offsets for a wall-clock timestamp via `offsets_for_times` if necessary.
+///
+async fn resolve_offsets<B>(broker: &B, topic: &str, partitions: &[i32], start_offset: &StartOffset) -> Result<HashMap<(String, i32), Offset>>
+where
+    B: PartitionAssigner,
+{
+    match start_offset {
+        StartOffset::Timestamp(timestamp) => {
+            let resolved = broker.offsets_for_times(topic, partitions, timestamp.timestamp_millis())?;
+            Ok(partitions.iter()
+                .map(|&partition| {
+                    let offset = resolved.get(&partition).copied().unwrap_or(Offset::End);
+                    ((topic.to_string(), partition), offset_for(&StartOffset::Resolved(offset)))
+                })
+                .collect())
+        }
+        _ => {
+            let offset = offset_for(start_offset);
+            Ok(partitions.iter().map(|&partition| ((topic.to_string(), partition), offset)).collect())
+        }
     }
 }
 
@@ -374,15 +618,91 @@ impl ClientContext for IamContext {
     }
 }
 
+///
+/// The key-to-partition strategies kiek can predict, selectable via a CLI flag so kiek's
+/// prediction matches whatever partitioner the producer that wrote the key actually used.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Partitioner {
+    /// Kafka's own default partitioner: `murmur2(key) & 0x7fffffff % n`
+    Default,
+    /// Legacy partitioner based on `java.lang.String.hashCode()`, used by some older Kafka clients
+    JavaHashCode,
+    /// Rendezvous / highest-random-weight hashing: `hash(key + partition)` per partition, highest wins
+    Rendezvous,
+}
+
+impl Partitioner {
+    /// Name used in warning messages, so a mismatch tells the user which strategy produced the expectation.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Partitioner::Default => "default",
+            Partitioner::JavaHashCode => "java hashCode",
+            Partitioner::Rendezvous => "rendezvous",
+        }
+    }
+}
+
+///
+/// Calculates the partition for a key with the given partitioner strategy.
+///
+pub fn partition_for_key(key: &str, partitions: usize, partitioner: Partitioner) -> usize {
+    match partitioner {
+        Partitioner::Default => default_partition_for_key(key, partitions),
+        Partitioner::JavaHashCode => java_hash_code_partition_for_key(key, partitions),
+        Partitioner::Rendezvous => rendezvous_partition_for_key(key, partitions),
+    }
+}
+
 ///
 /// Implementation of the partitioner algorithm used by Kafka's default partitioner
 ///
-pub fn partition_for_key(key: &str, partitions: usize) -> usize {
+fn default_partition_for_key(key: &str, partitions: usize) -> usize {
     let hash = murmur2(key.as_bytes(), KAFKA_SEED);
     let hash = hash & 0x7fffffff; // "to positive" from Kafka's partitioner
     (hash % partitions as u32) as usize
 }
 
+///
+/// Implementation of the legacy partitioner based on `java.lang.String.hashCode()`
+///
+fn java_hash_code_partition_for_key(key: &str, partitions: usize) -> usize {
+    let hash = java_string_hash_code(key);
+    let hash = hash & 0x7fffffff; // "to positive" from Kafka's partitioner
+    (hash as u32 % partitions as u32) as usize
+}
+
+/// Re-implementation of `java.lang.String.hashCode()`: `s[0]*31^(n-1) + ... + s[n-1]`, over UTF-16 code units
+fn java_string_hash_code(key: &str) -> i32 {
+    key.encode_utf16().fold(0i32, |hash, c| hash.wrapping_mul(31).wrapping_add(c as i32))
+}
+
+///
+/// Implementation of rendezvous / highest-random-weight (HRW) hashing: for every partition `p` in
+/// `0..n`, computes `w_p = murmur2(key_bytes ++ p)` and picks the partition with the maximum
+/// weight, breaking ties by the lowest partition index.
+///
+fn rendezvous_partition_for_key(key: &str, partitions: usize) -> usize {
+    let mut weighted = Vec::with_capacity(key.len() + 4);
+    weighted.extend_from_slice(key.as_bytes());
+
+    let mut best_partition = 0;
+    let mut best_weight = 0u32;
+
+    for partition in 0..partitions {
+        weighted.truncate(key.len());
+        weighted.extend_from_slice(&(partition as i32).to_be_bytes());
+
+        let weight = murmur2(&weighted, KAFKA_SEED);
+        if partition == 0 || weight > best_weight {
+            best_weight = weight;
+            best_partition = partition;
+        }
+    }
+
+    best_partition
+}
+
 ///
 /// Format a Kafka record timestamp for display
 ///
@@ -428,18 +748,251 @@ impl<'a> std::fmt::Display for FormatBootstrapServers<'a> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::cell::RefCell;
+
+    ///
+    /// In-memory fake broker for unit-testing the topic-selection, partition-assignment and
+    /// offset-resolution logic without a real Kafka cluster.
+    ///
+    struct MockBroker {
+        topics: HashMap<String, usize>,
+        timestamp_offsets: HashMap<(String, i32), Offset>,
+        watermarks: HashMap<(String, i32), (i64, i64)>,
+        committed: HashMap<(String, i32), Offset>,
+        assigned: RefCell<HashMap<(String, i32), Offset>>,
+    }
+
+    impl MockBroker {
+        fn new(topics: &[(&str, usize)]) -> Self {
+            Self {
+                topics: topics.iter().map(|&(name, partitions)| (name.to_string(), partitions)).collect(),
+                timestamp_offsets: HashMap::new(),
+                watermarks: HashMap::new(),
+                committed: HashMap::new(),
+                assigned: RefCell::new(HashMap::new()),
+            }
+        }
+
+        fn with_timestamp_offset(mut self, topic: &str, partition: i32, offset: Offset) -> Self {
+            self.timestamp_offsets.insert((topic.to_string(), partition), offset);
+            self
+        }
+
+        fn with_watermark(mut self, topic: &str, partition: i32, low: i64, high: i64) -> Self {
+            self.watermarks.insert((topic.to_string(), partition), (low, high));
+            self
+        }
+
+        fn with_committed(mut self, topic: &str, partition: i32, offset: i64) -> Self {
+            self.committed.insert((topic.to_string(), partition), Offset::Offset(offset));
+            self
+        }
+    }
+
+    impl MetadataSource for MockBroker {
+        fn fetch_topics(&self, topic: Option<&str>) -> Result<Vec<TopicInfo>> {
+            Ok(self.topics.iter()
+                .filter(|(name, _)| topic.map_or(true, |t| t == name.as_str()))
+                .map(|(name, &partitions)| TopicInfo { name: name.clone(), partitions })
+                .collect())
+        }
+    }
+
+    impl PartitionAssigner for MockBroker {
+        fn assign(&self, assignment: &HashMap<(String, i32), Offset>) -> Result<()> {
+            self.assigned.borrow_mut().extend(assignment.clone());
+            Ok(())
+        }
+
+        fn offsets_for_times(&self, topic: &str, partitions: &[i32], _timestamp_ms: i64) -> Result<HashMap<i32, Offset>> {
+            Ok(partitions.iter()
+                .map(|&partition| {
+                    let offset = self.timestamp_offsets.get(&(topic.to_string(), partition)).copied().unwrap_or(Offset::End);
+                    (partition, offset)
+                })
+                .collect())
+        }
+
+        fn committed_offsets(&self, topic: &str, partitions: &[i32]) -> Result<HashMap<i32, Offset>> {
+            Ok(partitions.iter()
+                .map(|&partition| {
+                    let offset = self.committed.get(&(topic.to_string(), partition)).copied().unwrap_or(Offset::Invalid);
+                    (partition, offset)
+                })
+                .collect())
+        }
+
+        fn fetch_watermarks(&self, topic: &str, partition: i32) -> Result<(i64, i64)> {
+            Ok(self.watermarks.get(&(topic.to_string(), partition)).copied().unwrap_or((0, 0)))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fetch_number_of_partitions_unknown_topic() {
+        let broker = MockBroker::new(&[("orders", 3)]);
+
+        let error = fetch_number_of_partitions(&broker, "orderz", &Highlighting::plain()).await.unwrap_err();
+        assert_eq!(error.to_string(), "Topic orderz does not exist. Did you mean orders?");
+    }
+
+    #[tokio::test]
+    async fn test_assign_topic_or_partition_out_of_range() {
+        let broker = MockBroker::new(&[("orders", 3)]);
+        let topic_or_partition = TopicOrPartition::TopicPartition("orders".to_string(), 5);
+
+        let error = assign_topic_or_partition(&broker, &topic_or_partition, StartOffset::Earliest, &Highlighting::plain()).await.unwrap_err();
+        assert_eq!(error.to_string(), "Partition 5 is out of range for orders with 3 partitions.");
+        assert!(broker.assigned.borrow().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_assign_topic_or_partition_assigns_all_partitions() {
+        let broker = MockBroker::new(&[("orders", 3)]);
+        let topic_or_partition = TopicOrPartition::Topic("orders".to_string());
+
+        assign_topic_or_partition(&broker, &topic_or_partition, StartOffset::Latest(0), &Highlighting::plain()).await.unwrap();
+
+        let assigned = broker.assigned.borrow();
+        assert_eq!(assigned.len(), 3);
+        for partition in 0..3 {
+            assert_eq!(assigned.get(&("orders".to_string(), partition)), Some(&Offset::End));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_assign_topic_or_partition_resolves_timestamp_per_partition() {
+        let broker = MockBroker::new(&[("orders", 2)])
+            .with_timestamp_offset("orders", 0, Offset::Offset(42));
+        let topic_or_partition = TopicOrPartition::TopicPartition("orders".to_string(), 0);
+
+        assign_topic_or_partition(&broker, &topic_or_partition, StartOffset::Timestamp(Local::now()), &Highlighting::plain()).await.unwrap();
+
+        assert_eq!(broker.assigned.borrow().get(&("orders".to_string(), 0)), Some(&Offset::Offset(42)));
+    }
+
+    #[tokio::test]
+    async fn test_assign_topic_or_partition_pattern_no_match() {
+        let broker = MockBroker::new(&[("orders", 3)]);
+        let topic_or_partition = TopicOrPartition::Pattern(Regex::new("^billing\\..*").unwrap());
+
+        let error = assign_topic_or_partition(&broker, &topic_or_partition, StartOffset::Earliest, &Highlighting::plain()).await.unwrap_err();
+        assert_eq!(error.to_string(), "No topics match pattern ^billing\\..*. 1 topics are available in total.");
+        assert!(broker.assigned.borrow().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_assign_topic_or_partition_pattern_matches_multiple_topics() {
+        let broker = MockBroker::new(&[("orders.created", 2), ("orders.cancelled", 3), ("billing.invoices", 1)]);
+        let topic_or_partition = TopicOrPartition::Pattern(Regex::new("^orders\\..*").unwrap());
+
+        assign_topic_or_partition(&broker, &topic_or_partition, StartOffset::Earliest, &Highlighting::plain()).await.unwrap();
+
+        let assigned = broker.assigned.borrow();
+        assert_eq!(assigned.len(), 5);
+        assert!(assigned.keys().all(|(topic, _)| topic != "billing.invoices"));
+        assert!(assigned.values().all(|&offset| offset == Offset::Beginning));
+    }
+
+    #[tokio::test]
+    async fn test_assign_partition_for_key_assigns_computed_partition() {
+        let broker = MockBroker::new(&[("orders", 6)]);
+        let topic_or_partition = TopicOrPartition::Topic("orders".to_string());
+        let feedback = Feedback { highlighting: Highlighting::plain(), silent: true };
+
+        assign_partition_for_key(&broker, &topic_or_partition, "control+e2e-zgddu3j-delete", Partitioner::Default, StartOffset::Latest(0), &feedback).await.unwrap();
+
+        let assigned = broker.assigned.borrow();
+        assert_eq!(assigned.len(), 1);
+        assert_eq!(assigned.get(&("orders".to_string(), 5)), Some(&Offset::End));
+    }
+
+    #[tokio::test]
+    async fn test_assign_partition_for_key_rejects_pattern() {
+        let broker = MockBroker::new(&[("orders", 3)]);
+        let topic_or_partition = TopicOrPartition::Pattern(Regex::new("^orders\\..*").unwrap());
+        let feedback = Feedback { highlighting: Highlighting::plain(), silent: true };
+
+        let error = assign_partition_for_key(&broker, &topic_or_partition, "some-key", Partitioner::Default, StartOffset::Earliest, &feedback).await.unwrap_err();
+        assert_eq!(error.to_string(), "Cannot look up key some-key in a topic pattern (^orders\\..*). Please specify a single topic.");
+        assert!(broker.assigned.borrow().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_assign_partition_for_key_honors_configured_partition_on_mismatch() {
+        let broker = MockBroker::new(&[("orders", 6)]);
+        let topic_or_partition = TopicOrPartition::TopicPartition("orders".to_string(), 0);
+        let feedback = Feedback { highlighting: Highlighting::plain(), silent: true };
+
+        assign_partition_for_key(&broker, &topic_or_partition, "control+e2e-zgddu3j-delete", Partitioner::Default, StartOffset::Earliest, &feedback).await.unwrap();
+
+        let assigned = broker.assigned.borrow();
+        assert_eq!(assigned.len(), 1);
+        assert_eq!(assigned.get(&("orders".to_string(), 0)), Some(&Offset::Beginning));
+    }
+
+    #[test]
+    fn test_committed_offsets_and_watermarks_for_lag() {
+        let broker = MockBroker::new(&[("orders", 2)])
+            .with_watermark("orders", 0, 0, 100)
+            .with_watermark("orders", 1, 0, 50)
+            .with_committed("orders", 0, 80);
+
+        let committed = broker.committed_offsets("orders", &[0, 1]).unwrap();
+        assert_eq!(committed.get(&0), Some(&Offset::Offset(80)));
+        assert_eq!(committed.get(&1), Some(&Offset::Invalid));
+
+        let (_, high) = broker.fetch_watermarks("orders", 0).unwrap();
+        assert_eq!(high, 100);
+    }
+
+    #[tokio::test]
+    async fn test_inspect_consumer_group_out_of_range_partition() {
+        let broker = MockBroker::new(&[("orders", 3)]);
+        let topic_or_partition = TopicOrPartition::TopicPartition("orders".to_string(), 3);
+        let feedback = Feedback { highlighting: Highlighting::plain(), silent: true };
+
+        let error = inspect_consumer_group(&broker, &"localhost:9092".to_string(), &topic_or_partition, &feedback).await.unwrap_err();
+        assert_eq!(error.to_string(), "Partition 3 is out of range for orders with 3 partitions.");
+    }
+
+    #[tokio::test]
+    async fn test_select_topic_or_partition_ambiguous_when_silent() {
+        let broker = MockBroker::new(&[("orders", 3), ("payments", 2)]);
+        let feedback = Feedback { highlighting: Highlighting::plain(), silent: true };
+
+        let error = select_topic_or_partition(&broker, &feedback).await.unwrap_err();
+        assert_eq!(error.to_string(), "Multiple topics available in the Kafka cluster. Please specify a topic.");
+    }
 
     #[test]
     fn test_partition_for_key() {
-        assert_eq!(partition_for_key("control+e2e-zgddu3j-delete", 6), 5);
-        assert_eq!(partition_for_key("control+e2e-6n304jn-delete", 6), 1);
-
-        assert_eq!(partition_for_key("834408c2-6061-7057-bdc1-320cc24c8873", 6), 3);
-        assert_eq!(partition_for_key("control+e2e-bgrl0v-delete", 6), 0);
-        assert_eq!(partition_for_key("control+e2e-5ijyt78-delete", 6), 5);
-        assert_eq!(partition_for_key("03b40832-b061-703d-4d68-895dee9e1f90", 6), 4);
-        assert_eq!(partition_for_key("control+e2e-86d8ra-delete", 6), 2);
-        assert_eq!(partition_for_key("control+e2e-lhwcl2-delete", 6), 1);
+        assert_eq!(partition_for_key("control+e2e-zgddu3j-delete", 6, Partitioner::Default), 5);
+        assert_eq!(partition_for_key("control+e2e-6n304jn-delete", 6, Partitioner::Default), 1);
+
+        assert_eq!(partition_for_key("834408c2-6061-7057-bdc1-320cc24c8873", 6, Partitioner::Default), 3);
+        assert_eq!(partition_for_key("control+e2e-bgrl0v-delete", 6, Partitioner::Default), 0);
+        assert_eq!(partition_for_key("control+e2e-5ijyt78-delete", 6, Partitioner::Default), 5);
+        assert_eq!(partition_for_key("03b40832-b061-703d-4d68-895dee9e1f90", 6, Partitioner::Default), 4);
+        assert_eq!(partition_for_key("control+e2e-86d8ra-delete", 6, Partitioner::Default), 2);
+        assert_eq!(partition_for_key("control+e2e-lhwcl2-delete", 6, Partitioner::Default), 1);
+    }
+
+    #[test]
+    fn test_partition_for_key_java_hash_code() {
+        assert_eq!(partition_for_key("control+e2e-zgddu3j-delete", 6, Partitioner::JavaHashCode), 2);
+        assert_eq!(partition_for_key("control+e2e-6n304jn-delete", 6, Partitioner::JavaHashCode), 2);
+        assert_eq!(partition_for_key("834408c2-6061-7057-bdc1-320cc24c8873", 6, Partitioner::JavaHashCode), 2);
+        assert_eq!(partition_for_key("control+e2e-bgrl0v-delete", 6, Partitioner::JavaHashCode), 2);
+        assert_eq!(partition_for_key("03b40832-b061-703d-4d68-895dee9e1f90", 6, Partitioner::JavaHashCode), 3);
+    }
+
+    #[test]
+    fn test_partition_for_key_rendezvous() {
+        assert_eq!(partition_for_key("control+e2e-zgddu3j-delete", 6, Partitioner::Rendezvous), 1);
+        assert_eq!(partition_for_key("control+e2e-6n304jn-delete", 6, Partitioner::Rendezvous), 1);
+        assert_eq!(partition_for_key("834408c2-6061-7057-bdc1-320cc24c8873", 6, Partitioner::Rendezvous), 5);
+        assert_eq!(partition_for_key("control+e2e-bgrl0v-delete", 6, Partitioner::Rendezvous), 0);
+        assert_eq!(partition_for_key("03b40832-b061-703d-4d68-895dee9e1f90", 6, Partitioner::Rendezvous), 0);
     }
 
     #[test]